@@ -0,0 +1,135 @@
+// Copyright 2018 TiKV Project Authors. Licensed under Apache-2.0.
+
+//! Cross-origin resource sharing for the status server.
+//!
+//! CORS is handled once, uniformly, for every route: [`CorsConfig`]
+//! answers `OPTIONS` preflight requests before a request ever reaches the
+//! [`super::router::Router`], and stamps the matching
+//! `Access-Control-Allow-Origin` header onto whatever response a handler
+//! produced. Handlers themselves stay unaware of CORS entirely.
+
+use hyper::header::{
+    HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ORIGIN, VARY,
+};
+use hyper::{Body, Method, Request, Response, StatusCode};
+
+/// The set of origins (or `"*"`) allowed to call the status server from a
+/// browser, configured by the operator alongside the status address.
+#[derive(Clone, Default)]
+pub struct CorsConfig {
+    allowed_origins: Vec<String>,
+}
+
+impl CorsConfig {
+    pub fn new(allowed_origins: Vec<String>) -> Self {
+        CorsConfig { allowed_origins }
+    }
+
+    /// The `Access-Control-Allow-Origin` value for `req`, if its `Origin`
+    /// header is present and in the allow-list.
+    pub fn allowed_origin_header(&self, req: &Request<Body>) -> Option<HeaderValue> {
+        let origin = req.headers().get(ORIGIN)?.to_str().ok()?;
+        if self.allowed_origins.iter().any(|o| o == "*" || o == origin) {
+            HeaderValue::from_str(origin).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Builds the response to an `OPTIONS` preflight request, or `None` if
+    /// `req` isn't one (or its `Origin` isn't allowed).
+    pub fn preflight_response(&self, req: &Request<Body>) -> Option<Response<Body>> {
+        if req.method() != Method::OPTIONS {
+            return None;
+        }
+        let origin = self.allowed_origin_header(req)?;
+        Some(
+            Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .header(ACCESS_CONTROL_ALLOW_ORIGIN, origin)
+                .header(ACCESS_CONTROL_ALLOW_METHODS, "GET, OPTIONS")
+                .header(ACCESS_CONTROL_ALLOW_HEADERS, "Content-Type")
+                .header(VARY, "Origin")
+                .body(Body::empty())
+                .unwrap(),
+        )
+    }
+
+    /// Attaches a previously computed `Access-Control-Allow-Origin` value
+    /// (see [`CorsConfig::allowed_origin_header`]) to `resp`, along with
+    /// `Vary: Origin` so caches don't serve one origin's response to
+    /// another when more than one origin is allowed.
+    pub fn apply(&self, origin: Option<HeaderValue>, mut resp: Response<Body>) -> Response<Body> {
+        if let Some(origin) = origin {
+            resp.headers_mut().insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        }
+        if !self.allowed_origins.is_empty() {
+            resp.headers_mut().insert(VARY, HeaderValue::from_static("Origin"));
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_with_origin(method: Method, origin: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri("/metrics")
+            .header(ORIGIN, origin)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_preflight_allowed_origin() {
+        let cors = CorsConfig::new(vec!["https://example.com".to_owned()]);
+        let req = request_with_origin(Method::OPTIONS, "https://example.com");
+        let resp = cors.preflight_response(&req).unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            resp.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://example.com"
+        );
+    }
+
+    #[test]
+    fn test_preflight_rejects_origin_not_in_allow_list() {
+        let cors = CorsConfig::new(vec!["https://example.com".to_owned()]);
+        let req = request_with_origin(Method::OPTIONS, "https://evil.example");
+        assert!(cors.preflight_response(&req).is_none());
+    }
+
+    #[test]
+    fn test_preflight_ignores_non_options_requests() {
+        let cors = CorsConfig::new(vec!["*".to_owned()]);
+        let req = request_with_origin(Method::GET, "https://example.com");
+        assert!(cors.preflight_response(&req).is_none());
+    }
+
+    #[test]
+    fn test_apply_sets_allow_origin_and_vary_when_configured() {
+        let cors = CorsConfig::new(vec!["https://example.com".to_owned()]);
+        let req = request_with_origin(Method::GET, "https://example.com");
+        let origin = cors.allowed_origin_header(&req);
+        let resp = cors.apply(origin, Response::new(Body::empty()));
+        assert_eq!(
+            resp.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://example.com"
+        );
+        assert_eq!(resp.headers().get(VARY).unwrap(), "Origin");
+    }
+
+    #[test]
+    fn test_apply_is_a_no_op_when_cors_is_not_configured() {
+        let cors = CorsConfig::default();
+        let req = request_with_origin(Method::GET, "https://example.com");
+        let origin = cors.allowed_origin_header(&req);
+        let resp = cors.apply(origin, Response::new(Body::empty()));
+        assert!(resp.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+        assert!(resp.headers().get(VARY).is_none());
+    }
+}
@@ -0,0 +1,106 @@
+// Copyright 2018 TiKV Project Authors. Licensed under Apache-2.0.
+
+//! A single place that turns handler failures into HTTP responses, so
+//! route handlers can `?`-propagate a [`StatusError`] instead of building
+//! a `Response` by hand at every failure site.
+
+use std::error::Error;
+
+use hyper::{Body, Response, StatusCode};
+use tikv_alloc::error::ProfError;
+
+pub enum StatusError {
+    BadRequest(String),
+    NotFound(String),
+    ProfilingDisabled(String),
+    Internal(Box<dyn Error + Send + Sync>),
+}
+
+impl From<ProfError> for StatusError {
+    fn from(err: ProfError) -> StatusError {
+        match err {
+            ProfError::MemProfilingNotEnabled => {
+                StatusError::ProfilingDisabled("feature mem-profiling is not enabled".to_owned())
+            }
+            ProfError::JemallocNotEnabled => {
+                StatusError::ProfilingDisabled("feature jemalloc is not enabled".to_owned())
+            }
+            ProfError::JemallocError(e) => StatusError::Internal(format!("jemalloc error {}", e).into()),
+        }
+    }
+}
+
+impl From<StatusError> for Response<Body> {
+    fn from(err: StatusError) -> Response<Body> {
+        let (status, body) = match err {
+            StatusError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            StatusError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+            StatusError::ProfilingDisabled(msg) => (StatusCode::NOT_FOUND, msg),
+            StatusError::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        };
+        Response::builder().status(status).body(Body::from(body)).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{Future, Stream};
+
+    fn body_of(resp: Response<Body>) -> String {
+        let body = resp.into_body().concat2().wait().unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_bad_request_maps_to_400() {
+        let resp = Response::from(StatusError::BadRequest("bad".to_owned()));
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body_of(resp), "bad");
+    }
+
+    #[test]
+    fn test_not_found_maps_to_404() {
+        let resp = Response::from(StatusError::NotFound("missing".to_owned()));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_of(resp), "missing");
+    }
+
+    #[test]
+    fn test_profiling_disabled_maps_to_404() {
+        let resp = Response::from(StatusError::ProfilingDisabled("disabled".to_owned()));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_of(resp), "disabled");
+    }
+
+    #[test]
+    fn test_internal_maps_to_500() {
+        let resp = Response::from(StatusError::Internal("boom".into()));
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body_of(resp), "boom");
+    }
+
+    #[test]
+    fn test_mem_profiling_not_enabled_maps_to_profiling_disabled() {
+        match StatusError::from(ProfError::MemProfilingNotEnabled) {
+            StatusError::ProfilingDisabled(_) => {}
+            _ => panic!("expected StatusError::ProfilingDisabled"),
+        }
+    }
+
+    #[test]
+    fn test_jemalloc_not_enabled_maps_to_profiling_disabled() {
+        match StatusError::from(ProfError::JemallocNotEnabled) {
+            StatusError::ProfilingDisabled(_) => {}
+            _ => panic!("expected StatusError::ProfilingDisabled"),
+        }
+    }
+
+    #[test]
+    fn test_jemalloc_error_maps_to_internal() {
+        match StatusError::from(ProfError::JemallocError("dump failed".to_owned())) {
+            StatusError::Internal(e) => assert!(e.to_string().contains("dump failed")),
+            _ => panic!("expected StatusError::Internal"),
+        }
+    }
+}
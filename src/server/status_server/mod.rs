@@ -0,0 +1,586 @@
+// Copyright 2018 TiKV Project Authors. Licensed under Apache-2.0.
+
+mod cors;
+mod error;
+#[macro_use]
+mod router;
+
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::future::ok;
+use futures::sync::oneshot::{Receiver, Sender};
+use futures::{self, Future, Stream};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::service_fn;
+use hyper::{self, Body, Method, Request, Response, Server, StatusCode};
+use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use rustls::{AllowAnyAuthenticatedClient, Certificate, NoClientAuth, PrivateKey, RootCertStore, ServerConfig as TlsConfig};
+use serde::Serialize;
+use serde_json;
+use tempdir::TempDir;
+use tokio_rustls::TlsAcceptor;
+use tokio_threadpool::{Builder, ThreadPool};
+
+use self::cors::CorsConfig;
+use self::error::StatusError;
+use self::router::{Context, HandlerFuture, Router};
+use super::Result;
+use crate::config::TiKvConfig;
+use tikv_alloc;
+use tikv_util::collections::HashMap;
+use tikv_util::metrics::dump;
+use tikv_util::security::SecurityConfig;
+use tikv_util::timer::GLOBAL_TIMER_HANDLE;
+
+/// Build metadata reported by `GET /status`, fixed at compile time: the
+/// version is Cargo's package version, and the git hash is read from the
+/// `TIKV_BUILD_GIT_HASH` environment variable (`"unknown"` when unset).
+const TIKV_VERSION: &str = env!("CARGO_PKG_VERSION");
+const TIKV_GIT_HASH: &str = match option_env!("TIKV_BUILD_GIT_HASH") {
+    Some(hash) => hash,
+    None => "unknown",
+};
+
+#[derive(Serialize)]
+struct StatusInfo {
+    version: &'static str,
+    git_hash: &'static str,
+    store_id: Option<u64>,
+    uptime_secs: u64,
+}
+
+/// Minimal region metadata returned by `GET /debug/region/{id}`. The real
+/// values are supplied by whoever owns the raftstore router, via
+/// [`StatusServer::set_region_info_provider`].
+#[derive(Serialize)]
+pub struct RegionDebugInfo {
+    pub region_id: u64,
+    pub start_key: String,
+    pub end_key: String,
+    pub leader_store_id: Option<u64>,
+}
+
+pub type RegionInfoProvider = Arc<dyn Fn(u64) -> Option<RegionDebugInfo> + Send + Sync>;
+
+pub struct StatusServer {
+    thread_pool: ThreadPool,
+    tx: Sender<()>,
+    rx: Option<Receiver<()>>,
+    addr: Option<SocketAddr>,
+    cfg: Arc<TiKvConfig>,
+    store_id: Option<u64>,
+    start_time: Instant,
+    region_info_provider: Option<RegionInfoProvider>,
+    cors: CorsConfig,
+}
+
+impl StatusServer {
+    pub fn new(status_thread_pool_size: usize, cfg: Arc<TiKvConfig>) -> Self {
+        let thread_pool = Builder::new()
+            .pool_size(status_thread_pool_size)
+            .name_prefix("status-server-")
+            .after_start(|| {
+                info!("Status server started");
+            })
+            .before_stop(|| {
+                info!("stopping status server");
+            })
+            .build();
+        let (tx, rx) = futures::sync::oneshot::channel::<()>();
+        StatusServer {
+            thread_pool,
+            tx,
+            rx: Some(rx),
+            addr: None,
+            cfg,
+            store_id: None,
+            start_time: Instant::now(),
+            region_info_provider: None,
+            cors: CorsConfig::default(),
+        }
+    }
+
+    pub fn set_store_id(&mut self, store_id: u64) {
+        self.store_id = Some(store_id);
+    }
+
+    pub fn set_region_info_provider(&mut self, provider: RegionInfoProvider) {
+        self.region_info_provider = Some(provider);
+    }
+
+    pub fn set_cors_allowed_origins(&mut self, allowed_origins: Vec<String>) {
+        self.cors = CorsConfig::new(allowed_origins);
+    }
+
+    // Build a `rustls::ServerConfig` from the same cert/key/CA material TiKV
+    // already uses to secure its gRPC endpoints, so operators only have to
+    // manage one set of certificates.
+    fn build_tls_acceptor(security_config: &SecurityConfig) -> Result<TlsAcceptor> {
+        let mut tls_config = if security_config.ca_path.is_empty() {
+            TlsConfig::new(NoClientAuth::new())
+        } else {
+            let mut root_store = RootCertStore::empty();
+            let ca_file = File::open(&security_config.ca_path)?;
+            root_store
+                .add_pem_file(&mut BufReader::new(ca_file))
+                .map_err(|_| box_err!("failed to load trusted CA for the status server"))?;
+            TlsConfig::new(AllowAnyAuthenticatedClient::new(root_store))
+        };
+
+        let cert_chain = Self::load_certs(&security_config.cert_path)?;
+        let key = Self::load_private_key(&security_config.key_path)?;
+        tls_config
+            .set_single_cert(cert_chain, key)
+            .map_err(|e| box_err!("failed to set certificate for the status server: {:?}", e))?;
+        Ok(TlsAcceptor::from(Arc::new(tls_config)))
+    }
+
+    fn load_certs(path: &str) -> Result<Vec<Certificate>> {
+        let cert_file = File::open(path)?;
+        certs(&mut BufReader::new(cert_file))
+            .map_err(|_| box_err!("failed to load certificate chain from {}", path))
+    }
+
+    fn load_private_key(path: &str) -> Result<PrivateKey> {
+        let key_file = File::open(path)?;
+        let mut reader = BufReader::new(key_file);
+        let pkcs8_keys = pkcs8_private_keys(&mut reader)
+            .map_err(|_| box_err!("failed to load PKCS8 private key from {}", path))?;
+        if let Some(key) = pkcs8_keys.into_iter().next() {
+            return Ok(key);
+        }
+
+        let key_file = File::open(path)?;
+        let mut reader = BufReader::new(key_file);
+        let rsa_keys = rsa_private_keys(&mut reader)
+            .map_err(|_| box_err!("failed to load RSA private key from {}", path))?;
+        rsa_keys
+            .into_iter()
+            .next()
+            .ok_or_else(|| box_err!("no private key found in {}", path))
+    }
+
+    fn build_router(&self) -> Router<StatusError> {
+        let mut router = Router::new();
+        let cfg = self.cfg.clone();
+        let store_id = self.store_id;
+        let start_time = self.start_time;
+        let region_info_provider = self.region_info_provider.clone();
+        status_routes! { router,
+            GET "/metrics" => metrics_handler,
+            GET "/jeprof" => jeprof_handler,
+            GET "/status" => move |ctx: Context| status_handler(ctx, store_id, start_time),
+            GET "/config" => move |ctx: Context| config_handler(ctx, cfg.clone()),
+            GET "/debug/region/{id}" => move |ctx: Context| {
+                debug_region_handler(ctx, region_info_provider.clone())
+            },
+        }
+        router
+    }
+
+    pub fn start(&mut self, status_addr: String, security_config: &SecurityConfig) -> Result<()> {
+        let addr = SocketAddr::from_str(&status_addr)?;
+        let incoming = AddrIncoming::bind(&addr)?;
+        self.addr = Some(incoming.local_addr());
+
+        let router = Arc::new(self.build_router());
+        let cors = self.cors.clone();
+        let service = move |req: Request<Body>| -> HandlerFuture<hyper::Error> {
+            let router = router.clone();
+            if let Some(resp) = cors.preflight_response(&req) {
+                return Box::new(ok(resp));
+            }
+            let cors_origin = cors.allowed_origin_header(&req);
+            match router.find(&req) {
+                Some((handler, path_params)) => {
+                    let query = parse_query(req.uri().query().unwrap_or(""));
+                    let ctx = Context {
+                        req,
+                        path_params,
+                        query,
+                    };
+                    let cors = cors.clone();
+                    Box::new(handler(ctx).then(move |result| {
+                        let resp = match result {
+                            Ok(resp) => resp,
+                            Err(err) => Response::from(err),
+                        };
+                        ok(cors.apply(cors_origin, resp))
+                    }))
+                }
+                None => {
+                    let response = Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()).unwrap();
+                    Box::new(ok(cors.apply(cors_origin, response)))
+                }
+            }
+        };
+
+        // Start to serve, wrapping each accepted connection in TLS when the
+        // status server has been configured with certificate material.
+        if security_config.cert_path.is_empty() || security_config.key_path.is_empty() {
+            let server = Server::builder(incoming).serve(move || {
+                let service = service.clone();
+                service_fn(service)
+            });
+            let graceful = server
+                .with_graceful_shutdown(self.rx.take().unwrap())
+                .map_err(|e| error!("Status server error: {:?}", e));
+            self.thread_pool.spawn(graceful);
+        } else {
+            let tls_acceptor = Self::build_tls_acceptor(security_config)?;
+            // A single bad handshake (a plain TCP probe, a health check, a
+            // client without a valid cert once mTLS is required) must not
+            // take down the accept loop for every other caller, so failed
+            // handshakes are logged and dropped instead of propagated as a
+            // stream error.
+            let tls_incoming = incoming
+                .map_err(|e| -> std::io::Error { e })
+                .and_then(move |conn| {
+                    tls_acceptor.accept(conn).then(|result| {
+                        if let Err(ref e) = result {
+                            warn!("failed TLS handshake on the status server: {:?}", e);
+                        }
+                        Ok(result.ok())
+                    })
+                })
+                .filter_map(|stream| stream);
+            let server = Server::builder(tls_incoming).serve(move || {
+                let service = service.clone();
+                service_fn(service)
+            });
+            let graceful = server
+                .with_graceful_shutdown(self.rx.take().unwrap())
+                .map_err(|e| error!("Status server error: {:?}", e));
+            self.thread_pool.spawn(graceful);
+        }
+        Ok(())
+    }
+
+    pub fn stop(self) {
+        let _ = self.tx.send(());
+        self.thread_pool
+            .shutdown_now()
+            .wait()
+            .unwrap_or_else(|e| error!("failed to stop the status server, error: {:?}", e));
+    }
+
+    // Return listening address, this may only be used for outer test
+    // to get the real address because we may use "127.0.0.1:0"
+    // in test to avoid port conflict.
+    pub fn listening_addr(&self) -> SocketAddr {
+        self.addr.unwrap()
+    }
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect()
+}
+
+fn metrics_handler(_ctx: Context) -> HandlerFuture<StatusError> {
+    let response = Response::builder().body(Body::from(dump())).unwrap();
+    Box::new(ok(response))
+}
+
+fn status_handler(_ctx: Context, store_id: Option<u64>, start_time: Instant) -> HandlerFuture<StatusError> {
+    let info = StatusInfo {
+        version: TIKV_VERSION,
+        git_hash: TIKV_GIT_HASH,
+        store_id,
+        uptime_secs: start_time.elapsed().as_secs(),
+    };
+    match serde_json::to_vec(&info) {
+        Ok(body) => Box::new(ok(json_response(body))),
+        Err(e) => Box::new(futures::future::err(StatusError::Internal(Box::new(e)))),
+    }
+}
+
+fn config_handler(_ctx: Context, cfg: Arc<TiKvConfig>) -> HandlerFuture<StatusError> {
+    match serde_json::to_vec(&*cfg) {
+        Ok(body) => Box::new(ok(json_response(body))),
+        Err(e) => Box::new(futures::future::err(StatusError::Internal(Box::new(e)))),
+    }
+}
+
+fn debug_region_handler(
+    ctx: Context,
+    region_info_provider: Option<RegionInfoProvider>,
+) -> HandlerFuture<StatusError> {
+    let region_id: u64 = match ctx.path_param("id").and_then(|id| id.parse().ok()) {
+        Some(id) => id,
+        None => {
+            return Box::new(futures::future::err(StatusError::BadRequest(
+                "invalid region id".to_owned(),
+            )))
+        }
+    };
+    let region = region_info_provider.as_ref().and_then(|provider| provider(region_id));
+    match region {
+        Some(region) => match serde_json::to_vec(&region) {
+            Ok(body) => Box::new(ok(json_response(body))),
+            Err(e) => Box::new(futures::future::err(StatusError::Internal(Box::new(e)))),
+        },
+        None => Box::new(futures::future::err(StatusError::NotFound(format!(
+            "region {} not found",
+            region_id
+        )))),
+    }
+}
+
+fn json_response(body: Vec<u8>) -> Response<Body> {
+    Response::builder()
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn parse_seconds(ctx: &Context) -> std::result::Result<u64, StatusError> {
+    let val = ctx
+        .query("seconds")
+        .ok_or_else(|| StatusError::BadRequest("missing `seconds` query parameter".to_owned()))?;
+    val.parse()
+        .map_err(|_| StatusError::BadRequest("invalid `seconds` query parameter".to_owned()))
+}
+
+fn jeprof_handler(ctx: Context) -> HandlerFuture<StatusError> {
+    let seconds = match parse_seconds(&ctx) {
+        Ok(seconds) => seconds,
+        Err(e) => return Box::new(futures::future::err(e)),
+    };
+    if let Err(e) = tikv_alloc::activate_prof() {
+        return Box::new(futures::future::err(StatusError::from(e)));
+    }
+    info!("Start profiling {} seconds", seconds);
+
+    let timer = GLOBAL_TIMER_HANDLE.clone();
+    Box::new(
+        timer
+            .delay(std::time::Instant::now() + std::time::Duration::from_secs(seconds))
+            .then(|result| {
+                // The timer itself failing still means profiling was left
+                // active; turn it back off before reporting the error.
+                if result.is_err() {
+                    if let Err(e) = tikv_alloc::deactivate_prof() {
+                        error!("deactivate_prof failed after a timer error: {:?}", e);
+                    }
+                }
+                result.map_err(|e| StatusError::Internal(Box::new(e)))
+            })
+            .and_then(|_| {
+                tikv_alloc::deactivate_prof()?;
+
+                let tmp_dir = TempDir::new("").map_err(|e| StatusError::Internal(Box::new(e)))?;
+                let os_path = tmp_dir.path().join("tikv_dump_profile").into_os_string();
+                let path = os_path.into_string().unwrap();
+
+                tikv_alloc::dump_prof(Some(&path));
+                Ok((tmp_dir, path))
+            })
+            .and_then(|(tmp_dir, path)| {
+                tokio_fs::file::File::open(path)
+                    .and_then(|file| {
+                        let buf: Vec<u8> = Vec::new();
+                        tokio_io::io::read_to_end(file, buf)
+                    })
+                    .map(move |(_, buf)| {
+                        let response = Response::builder().body(buf.into()).unwrap();
+                        drop(tmp_dir); // Drop here manually to extend life of tmp_dir.
+                        response
+                    })
+                    .map_err(|e| StatusError::Internal(Box::new(e)))
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TiKvConfig;
+    use crate::server::status_server::StatusServer;
+    use futures::future::{lazy, Future};
+    use hyper::{Client, StatusCode, Uri};
+    use std::sync::Arc;
+    use tikv_util::security::SecurityConfig;
+
+    fn empty_context() -> Context {
+        Context {
+            req: Request::builder().body(Body::empty()).unwrap(),
+            path_params: HashMap::default(),
+            query: HashMap::default(),
+        }
+    }
+
+    #[test]
+    fn test_status_handler_reports_version_and_store_id() {
+        let resp = status_handler(empty_context(), Some(7), Instant::now())
+            .wait()
+            .unwrap();
+        let body = resp.into_body().concat2().wait().unwrap();
+        let info: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info["version"], TIKV_VERSION);
+        assert_eq!(info["store_id"], 7);
+    }
+
+    #[test]
+    fn test_config_handler_returns_the_effective_config() {
+        let resp = config_handler(empty_context(), Arc::new(TiKvConfig::default()))
+            .wait()
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_debug_region_handler_not_found_without_provider() {
+        let mut ctx = empty_context();
+        ctx.path_params.insert("id".to_owned(), "1".to_owned());
+        let err = debug_region_handler(ctx, None).wait().unwrap_err();
+        match err {
+            StatusError::NotFound(_) => {}
+            _ => panic!("expected StatusError::NotFound"),
+        }
+    }
+
+    #[test]
+    fn test_debug_region_handler_returns_region_from_provider() {
+        let mut ctx = empty_context();
+        ctx.path_params.insert("id".to_owned(), "1".to_owned());
+        let provider: RegionInfoProvider = Arc::new(|region_id| {
+            Some(RegionDebugInfo {
+                region_id,
+                start_key: String::new(),
+                end_key: String::new(),
+                leader_store_id: Some(1),
+            })
+        });
+        let resp = debug_region_handler(ctx, Some(provider)).wait().unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_debug_region_handler_rejects_non_numeric_id() {
+        let mut ctx = empty_context();
+        ctx.path_params.insert("id".to_owned(), "not-a-number".to_owned());
+        let err = debug_region_handler(ctx, None).wait().unwrap_err();
+        match err {
+            StatusError::BadRequest(_) => {}
+            _ => panic!("expected StatusError::BadRequest"),
+        }
+    }
+
+    // A self-signed cert/key pair used only by the tests below.
+    const TEST_CERT_PEM: &str = include_str!("testdata/cert.pem");
+    const TEST_KEY_PEM: &str = include_str!("testdata/key.pem");
+
+    fn write_test_cert_and_key(dir: &TempDir) -> (String, String) {
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        std::fs::write(&cert_path, TEST_CERT_PEM).unwrap();
+        std::fs::write(&key_path, TEST_KEY_PEM).unwrap();
+        (
+            cert_path.to_str().unwrap().to_owned(),
+            key_path.to_str().unwrap().to_owned(),
+        )
+    }
+
+    #[test]
+    fn test_build_tls_acceptor_rejects_missing_cert() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let (_, key_path) = write_test_cert_and_key(&tmp_dir);
+        let mut security_config = SecurityConfig::default();
+        security_config.cert_path = tmp_dir.path().join("no-such-cert.pem").to_str().unwrap().to_owned();
+        security_config.key_path = key_path;
+        assert!(StatusServer::build_tls_acceptor(&security_config).is_err());
+    }
+
+    #[test]
+    fn test_build_tls_acceptor_rejects_missing_key() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let (cert_path, _) = write_test_cert_and_key(&tmp_dir);
+        let mut security_config = SecurityConfig::default();
+        security_config.cert_path = cert_path;
+        security_config.key_path = tmp_dir.path().join("no-such-key.pem").to_str().unwrap().to_owned();
+        assert!(StatusServer::build_tls_acceptor(&security_config).is_err());
+    }
+
+    #[test]
+    fn test_build_tls_acceptor_rejects_missing_ca() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let (cert_path, key_path) = write_test_cert_and_key(&tmp_dir);
+        let mut security_config = SecurityConfig::default();
+        security_config.cert_path = cert_path;
+        security_config.key_path = key_path;
+        security_config.ca_path = tmp_dir.path().join("no-such-ca.pem").to_str().unwrap().to_owned();
+        assert!(StatusServer::build_tls_acceptor(&security_config).is_err());
+    }
+
+    #[test]
+    fn test_build_tls_acceptor_builds_with_no_client_auth() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let (cert_path, key_path) = write_test_cert_and_key(&tmp_dir);
+        let mut security_config = SecurityConfig::default();
+        security_config.cert_path = cert_path;
+        security_config.key_path = key_path;
+        assert!(security_config.ca_path.is_empty());
+        StatusServer::build_tls_acceptor(&security_config).unwrap();
+    }
+
+    #[test]
+    fn test_build_tls_acceptor_builds_with_client_auth_required() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let (cert_path, key_path) = write_test_cert_and_key(&tmp_dir);
+        let mut security_config = SecurityConfig::default();
+        security_config.ca_path = cert_path.clone();
+        security_config.cert_path = cert_path;
+        security_config.key_path = key_path;
+        StatusServer::build_tls_acceptor(&security_config).unwrap();
+    }
+
+    #[test]
+    fn test_status_service_over_tls() {
+        let tmp_dir = TempDir::new("").unwrap();
+        let (cert_path, key_path) = write_test_cert_and_key(&tmp_dir);
+        let mut security_config = SecurityConfig::default();
+        security_config.cert_path = cert_path;
+        security_config.key_path = key_path;
+
+        let mut status_server = StatusServer::new(1, Arc::new(TiKvConfig::default()));
+        // The TLS branch of `start` must build the acceptor and spawn the
+        // server the same way the plaintext one does, without erroring out.
+        status_server
+            .start("127.0.0.1:0".to_string(), &security_config)
+            .unwrap();
+        status_server.stop();
+    }
+
+    #[test]
+    fn test_status_service() {
+        let mut status_server = StatusServer::new(1, Arc::new(TiKvConfig::default()));
+        let _ = status_server.start("127.0.0.1:0".to_string(), &SecurityConfig::default());
+        let client = Client::new();
+        let uri = Uri::builder()
+            .scheme("http")
+            .authority(status_server.listening_addr().to_string().as_str())
+            .path_and_query("/metrics")
+            .build()
+            .unwrap();
+
+        let handle = status_server.thread_pool.spawn_handle(lazy(move || {
+            client
+                .get(uri)
+                .map(|res| {
+                    assert_eq!(res.status(), StatusCode::OK);
+                })
+                .map_err(|err| {
+                    panic!("response status is not OK: {:?}", err);
+                })
+        }));
+        handle.wait().unwrap();
+        status_server.stop();
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2018 TiKV Project Authors. Licensed under Apache-2.0.
+
+//! A small declarative router for the status server.
+//!
+//! Endpoints are registered once via [`Router::register`] (or the
+//! [`status_routes`] macro) instead of being hand-written as arms of a
+//! giant `match (method, path)`. Handlers receive a parsed [`Context`]
+//! (path params, decoded query string) and return a `Response` or an
+//! error that the caller renders into one.
+
+use std::sync::Arc;
+
+use futures::Future;
+use hyper::{Body, Method, Request, Response};
+use tikv_util::collections::HashMap;
+
+pub type HandlerFuture<E> = Box<dyn Future<Item = Response<Body>, Error = E> + Send>;
+pub type Handler<E> = Arc<dyn Fn(Context) -> HandlerFuture<E> + Send + Sync>;
+
+/// The parsed request handed to a route handler: the original request
+/// (so the handler can still read headers/body), path params extracted
+/// from `{name}` segments of the route pattern, and the decoded query
+/// string.
+pub struct Context {
+    pub req: Request<Body>,
+    pub path_params: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+}
+
+impl Context {
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query.get(name).map(String::as_str)
+    }
+
+    pub fn path_param(&self, name: &str) -> Option<&str> {
+        self.path_params.get(name).map(String::as_str)
+    }
+}
+
+// A single path segment of a registered route: either a literal that must
+// match exactly, or a `{name}` capture.
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+struct Pattern {
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    fn parse(pattern: &str) -> Pattern {
+        let segments = pattern
+            .trim_matches('/')
+            .split('/')
+            .map(|part| {
+                if part.starts_with('{') && part.ends_with('}') {
+                    Segment::Param(part[1..part.len() - 1].to_owned())
+                } else {
+                    Segment::Literal(part.to_owned())
+                }
+            })
+            .collect();
+        Pattern { segments }
+    }
+
+    fn matches(&self, path: &str) -> Option<HashMap<String, String>> {
+        let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+        if parts.len() != self.segments.len() {
+            return None;
+        }
+        let mut params = HashMap::default();
+        for (segment, part) in self.segments.iter().zip(parts.iter()) {
+            match segment {
+                Segment::Literal(lit) => {
+                    if lit != part {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    params.insert(name.clone(), (*part).to_owned());
+                }
+            }
+        }
+        Some(params)
+    }
+}
+
+struct Route<E> {
+    method: Method,
+    pattern: Pattern,
+    handler: Handler<E>,
+}
+
+/// A table mapping `(Method, path-pattern)` to handlers, replacing the
+/// monolithic match in [`super::StatusServer::start`].
+#[derive(Default)]
+pub struct Router<E> {
+    routes: Vec<Route<E>>,
+}
+
+impl<E> Router<E> {
+    pub fn new() -> Self {
+        Router { routes: Vec::new() }
+    }
+
+    pub fn register<F>(&mut self, method: Method, pattern: &str, handler: F)
+    where
+        F: Fn(Context) -> HandlerFuture<E> + Send + Sync + 'static,
+    {
+        self.routes.push(Route {
+            method,
+            pattern: Pattern::parse(pattern),
+            handler: Arc::new(handler),
+        });
+    }
+
+    /// Finds the handler matching `req`'s method and path, returning the
+    /// handler together with the path params captured from the pattern.
+    pub fn find(&self, req: &Request<Body>) -> Option<(Handler<E>, HashMap<String, String>)> {
+        let path = req.uri().path();
+        self.routes
+            .iter()
+            .filter(|route| &route.method == req.method())
+            .find_map(|route| {
+                route
+                    .pattern
+                    .matches(path)
+                    .map(|params| (route.handler.clone(), params))
+            })
+    }
+}
+
+/// Registers several routes in one block instead of one `register` call
+/// per line, e.g.:
+///
+/// ```ignore
+/// status_routes! { router,
+///     GET "/metrics" => metrics_handler,
+///     GET "/status" => status_handler,
+/// }
+/// ```
+#[macro_export]
+macro_rules! status_routes {
+    ($router:expr, $($method:ident $path:expr => $handler:expr),* $(,)?) => {
+        $( $router.register(hyper::Method::$method, $path, $handler); )*
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::ok;
+
+    fn ok_handler(_ctx: Context) -> HandlerFuture<()> {
+        Box::new(ok(Response::new(Body::empty())))
+    }
+
+    fn build_router() -> Router<()> {
+        let mut router = Router::new();
+        router.register(Method::GET, "/status", ok_handler);
+        router.register(Method::GET, "/debug/region/{id}", ok_handler);
+        router
+    }
+
+    #[test]
+    fn test_find_matches_literal_route() {
+        let router = build_router();
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/status")
+            .body(Body::empty())
+            .unwrap();
+        let (_, params) = router.find(&req).unwrap();
+        assert!(params.is_empty());
+    }
+
+    #[test]
+    fn test_find_extracts_path_params() {
+        let router = build_router();
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/debug/region/42")
+            .body(Body::empty())
+            .unwrap();
+        let (_, params) = router.find(&req).unwrap();
+        assert_eq!(params.get("id").map(String::as_str), Some("42"));
+    }
+
+    #[test]
+    fn test_find_returns_none_for_unknown_path() {
+        let router = build_router();
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri("/no-such-route")
+            .body(Body::empty())
+            .unwrap();
+        assert!(router.find(&req).is_none());
+    }
+
+    #[test]
+    fn test_find_returns_none_for_wrong_method() {
+        let router = build_router();
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("/status")
+            .body(Body::empty())
+            .unwrap();
+        assert!(router.find(&req).is_none());
+    }
+}